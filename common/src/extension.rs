@@ -0,0 +1,287 @@
+use std::collections::{HashMap, VecDeque};
+use std::convert::Infallible;
+use std::env;
+use std::net::SocketAddr;
+use std::sync::{Mutex, OnceLock};
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server};
+use opentelemetry::trace::{Tracer as _, TracerProvider as _};
+use opentelemetry::{Context, KeyValue};
+use opentelemetry_sdk::trace::{Tracer, TracerProvider};
+use serde::Deserialize;
+use serde_json::json;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+const EXTENSION_NAME: &str = "otel-platform-telemetry";
+const LISTENER_PORT: u16 = 4323;
+
+/// Caps how many invocation contexts `InvocationContexts` keeps around at once. Telemetry
+/// delivery is buffered and best-effort, so the `platform.report` for an invocation — especially
+/// the last one before the sandbox freezes — frequently never arrives; without a cap the
+/// registry would grow for as long as the sandbox stays warm.
+const MAX_TRACKED_INVOCATIONS: usize = 16;
+
+/// Invocation contexts keyed by Lambda request ID, so the platform telemetry for a given
+/// invocation (delivered later, out of band, on the Telemetry API listener) can be attached to
+/// the same trace the handler itself recorded under. Bounded to `MAX_TRACKED_INVOCATIONS`
+/// entries, evicting the oldest once full, since entries for invocations whose telemetry never
+/// arrives are otherwise never removed.
+#[derive(Default)]
+struct InvocationContexts {
+    order: VecDeque<String>,
+    contexts: HashMap<String, Context>,
+}
+
+impl InvocationContexts {
+    fn insert(&mut self, request_id: String, context: Context) {
+        if self.contexts.insert(request_id.clone(), context).is_none() {
+            self.order.push_back(request_id);
+        }
+        while self.order.len() > MAX_TRACKED_INVOCATIONS {
+            if let Some(oldest) = self.order.pop_front() {
+                self.contexts.remove(&oldest);
+            }
+        }
+    }
+
+    fn take(&mut self, request_id: &str) -> Option<Context> {
+        let context = self.contexts.remove(request_id)?;
+        self.order.retain(|id| id != request_id);
+        Some(context)
+    }
+
+    fn clear(&mut self) {
+        self.order.clear();
+        self.contexts.clear();
+    }
+}
+
+fn invocation_contexts() -> &'static Mutex<InvocationContexts> {
+    static REGISTRY: OnceLock<Mutex<InvocationContexts>> = OnceLock::new();
+    REGISTRY.get_or_init(|| Mutex::new(InvocationContexts::default()))
+}
+
+/// Records the span context for an in-flight invocation so `emit_span` can later parent the
+/// platform telemetry for this request onto it. Call this from the handler, before `await`ing
+/// any work, with `Span::current().context()`.
+pub fn record_invocation_context(request_id: impl Into<String>, context: Context) {
+    invocation_contexts()
+        .lock()
+        .unwrap()
+        .insert(request_id.into(), context);
+}
+
+/// Looks up the invocation context recorded for `request_id`. Several platform events carry the
+/// same request ID (`platform.start`, `platform.runtimeDone`, `platform.report`), so the entry is
+/// kept for all of them and removed once `platform.report`, the last of them, is handled.
+fn lookup_invocation_context(request_id: &str, last_event: bool) -> Option<Context> {
+    let mut registry = invocation_contexts().lock().unwrap();
+    if last_event {
+        registry.take(request_id)
+    } else {
+        registry.contexts.get(request_id).cloned()
+    }
+}
+
+#[derive(Deserialize)]
+struct TelemetryEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    record: serde_json::Value,
+}
+
+enum NextEvent {
+    Invoke,
+    Shutdown,
+}
+
+/// Registers this process as a Lambda internal extension subscribed to the Telemetry API's
+/// `platform` stream, so init/invoke/shutdown telemetry (cold starts, init duration, billed
+/// duration) shows up as spans alongside the handler's own traces. Runs on its own tokio task;
+/// a failure here is logged and never blocks invocations.
+pub fn spawn(provider: TracerProvider) {
+    tokio::spawn(async move {
+        if let Err(e) = run(provider).await {
+            tracing::warn!("platform telemetry extension stopped: {}", e);
+        }
+    });
+}
+
+async fn run(provider: TracerProvider) -> Result<(), BoxError> {
+    let runtime_api =
+        env::var("AWS_LAMBDA_RUNTIME_API").expect("AWS_LAMBDA_RUNTIME_API is required");
+    let client = reqwest::Client::new();
+    let tracer = provider.tracer("lambda-platform-telemetry");
+
+    let listener_addr = start_listener(tracer).await?;
+    let extension_id = register(&client, &runtime_api).await?;
+    subscribe(&client, &runtime_api, &extension_id, listener_addr.port()).await?;
+
+    loop {
+        match next_event(&client, &runtime_api, &extension_id).await? {
+            NextEvent::Shutdown => break,
+            NextEvent::Invoke => {}
+        }
+    }
+    invocation_contexts().lock().unwrap().clear();
+    Ok(())
+}
+
+async fn register(client: &reqwest::Client, runtime_api: &str) -> Result<String, BoxError> {
+    let response = client
+        .post(format!(
+            "http://{}/2020-01-01/extension/register",
+            runtime_api
+        ))
+        .header("Lambda-Extension-Name", EXTENSION_NAME)
+        .json(&json!({ "events": ["INVOKE", "SHUTDOWN"] }))
+        .send()
+        .await?
+        .error_for_status()?;
+
+    let extension_id = response
+        .headers()
+        .get("Lambda-Extension-Identifier")
+        .ok_or("registration response is missing Lambda-Extension-Identifier")?
+        .to_str()?
+        .to_string();
+    Ok(extension_id)
+}
+
+async fn subscribe(
+    client: &reqwest::Client,
+    runtime_api: &str,
+    extension_id: &str,
+    listener_port: u16,
+) -> Result<(), BoxError> {
+    client
+        .put(format!("http://{}/2022-07-01/telemetry", runtime_api))
+        .header("Lambda-Extension-Identifier", extension_id)
+        .json(&json!({
+            "schemaVersion": "2022-12-13",
+            "types": ["platform"],
+            "buffering": {
+                "timeoutMs": 1000,
+                "maxItems": 1000,
+                "maxBytes": 262_144,
+            },
+            "destination": {
+                "protocol": "HTTP",
+                "URI": format!("http://sandbox.localdomain:{}/telemetry", listener_port),
+            },
+        }))
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+async fn next_event(
+    client: &reqwest::Client,
+    runtime_api: &str,
+    extension_id: &str,
+) -> Result<NextEvent, BoxError> {
+    let response = client
+        .get(format!(
+            "http://{}/2020-01-01/extension/event/next",
+            runtime_api
+        ))
+        .header("Lambda-Extension-Identifier", extension_id)
+        .send()
+        .await?;
+    let body: serde_json::Value = response.json().await?;
+    match body.get("eventType").and_then(|v| v.as_str()) {
+        Some("SHUTDOWN") => Ok(NextEvent::Shutdown),
+        _ => Ok(NextEvent::Invoke),
+    }
+}
+
+/// Starts the local HTTP endpoint the Telemetry API delivers event batches to. The handler
+/// acknowledges every request immediately after converting it into spans so it never becomes
+/// the thing the runtime is waiting on.
+async fn start_listener(tracer: Tracer) -> Result<SocketAddr, BoxError> {
+    // Bind every interface rather than just loopback: the runtime delivers telemetry to the
+    // host we advertise in `subscribe` (`sandbox.localdomain`), which doesn't resolve to
+    // 127.0.0.1 inside the execution environment.
+    let addr: SocketAddr = ([0, 0, 0, 0], LISTENER_PORT).into();
+    let make_svc = make_service_fn(move |_conn| {
+        let tracer = tracer.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let tracer = tracer.clone();
+                handle_telemetry_request(req, tracer)
+            }))
+        }
+    });
+
+    let server = Server::bind(&addr).serve(make_svc);
+    let bound_addr = server.local_addr();
+    tokio::spawn(async move {
+        if let Err(e) = server.await {
+            tracing::warn!("telemetry listener stopped: {}", e);
+        }
+    });
+    Ok(bound_addr)
+}
+
+async fn handle_telemetry_request(
+    req: Request<Body>,
+    tracer: Tracer,
+) -> Result<Response<Body>, Infallible> {
+    let events: Vec<TelemetryEvent> = match hyper::body::to_bytes(req.into_body()).await {
+        Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_default(),
+        Err(_) => Vec::new(),
+    };
+    for event in &events {
+        emit_span(&tracer, event);
+    }
+    Ok(Response::new(Body::from("OK")))
+}
+
+fn emit_span(tracer: &Tracer, event: &TelemetryEvent) {
+    let mut attributes = Vec::new();
+    collect_attributes(&event.record, "faas", &mut attributes);
+
+    let request_id = event
+        .record
+        .get("requestId")
+        .and_then(|v| v.as_str());
+    let parent_cx = request_id
+        .and_then(|id| lookup_invocation_context(id, event.kind == "platform.report"))
+        .unwrap_or_else(Context::current);
+
+    tracer
+        .span_builder(event.kind.clone())
+        .with_attributes(attributes)
+        .start_with_context(tracer, &parent_cx)
+        .end();
+}
+
+/// Flattens a telemetry record's scalar fields into `KeyValue`s under `prefix`, descending into
+/// nested objects at any depth (such as `platform.runtimeDone`/`platform.report`'s `metrics` object,
+/// which carries `durationMs`, `billedDurationMs`, `initDurationMs`, and
+/// `platform.initRuntimeDone`'s `initializationType`/cold-start fields) rather than dropping them,
+/// since those nested values are the whole point of surfacing this telemetry as spans.
+fn collect_attributes(value: &serde_json::Value, prefix: &str, out: &mut Vec<KeyValue>) {
+    let Some(object) = value.as_object() else {
+        return;
+    };
+    for (key, value) in object {
+        if value.is_object() {
+            collect_attributes(value, &format!("{}.{}", prefix, key), out);
+        } else if let Some(v) = record_attribute(value) {
+            out.push(KeyValue::new(format!("{}.{}", prefix, key), v));
+        }
+    }
+}
+
+fn record_attribute(value: &serde_json::Value) -> Option<opentelemetry::Value> {
+    match value {
+        serde_json::Value::String(s) => Some(opentelemetry::Value::String(s.clone().into())),
+        serde_json::Value::Number(n) => n.as_f64().map(opentelemetry::Value::F64),
+        serde_json::Value::Bool(b) => Some(opentelemetry::Value::Bool(*b)),
+        _ => None,
+    }
+}
@@ -0,0 +1,4 @@
+pub mod extension;
+pub mod middleware;
+pub mod propagation;
+pub mod telemetry;
@@ -0,0 +1,81 @@
+use http::HeaderMap;
+use opentelemetry::propagation::{Extractor, TextMapPropagator};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// Adapts an `http::HeaderMap` to `opentelemetry::propagation::Extractor` so a W3C trace
+/// context can be pulled out of all incoming request headers, not just `traceparent`.
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl<'a> Extractor for HeaderExtractor<'a> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|v| v.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|k| k.as_str()).collect()
+    }
+}
+
+/// Wraps an async Lambda handler in the `Handler` span, declaring the exact set of HTTP
+/// semantic-convention fields `record_server_span`/`record_response_status` record. `Span::record`
+/// is a no-op for a field the span's metadata doesn't already declare, so that field set has to
+/// live in exactly one place rather than being hand-copied into every handler's `#[instrument]` --
+/// a copy that drifts out of sync with `record_server_span` would silently stop recording.
+/// Use in place of `#[instrument(name = "Handler")]` on the API Gateway and `lambda_http` entry
+/// points:
+///
+/// ```ignore
+/// common::instrument_handler! {
+/// async fn handler(request: Request) -> Result<Response<Body>, Error> {
+///     ...
+/// }
+/// }
+/// ```
+#[macro_export]
+macro_rules! instrument_handler {
+    ($vis:vis async fn $name:ident($($arg:ident : $ty:ty),* $(,)?) -> $ret:ty $body:block) => {
+        #[tracing::instrument(
+            name = "Handler",
+            fields(
+                otel.kind,
+                http.request.method = tracing::field::Empty,
+                http.route = tracing::field::Empty,
+                url.path = tracing::field::Empty,
+                http.response.status_code = tracing::field::Empty,
+                service = tracing::field::Empty,
+            )
+        )]
+        $vis async fn $name($($arg: $ty),*) -> $ret $body
+    };
+}
+
+/// Marks the current span as a SERVER span per HTTP semantic conventions, inspired by the Poem
+/// OpenTelemetry middleware: extracts the incoming trace context from `headers` and sets it as
+/// the span's parent, starting a fresh root span instead of panicking when `traceparent` is
+/// missing, and records `http.request.method`, `http.route` (when a templated route is known),
+/// `url.path`, and a `service` span attribute from `FUNCTION_NAME`. Call
+/// `record_response_status` once the response status is known. Shared so the API Gateway
+/// function and the `lambda_http` producer wrap their handlers the same way. The enclosing
+/// handler must be wrapped in `instrument_handler!` so the fields recorded here are declared.
+pub fn record_server_span(headers: &HeaderMap, method: &str, route: Option<&str>, path: &str) {
+    let propagator = TraceContextPropagator::new();
+    let context = propagator.extract(&HeaderExtractor(headers));
+
+    let span = Span::current();
+    span.set_parent(context);
+    span.record("otel.kind", "SERVER");
+    span.record("http.request.method", method);
+    if let Some(route) = route {
+        span.record("http.route", route);
+    }
+    span.record("url.path", path);
+    if let Ok(service) = std::env::var("FUNCTION_NAME") {
+        span.record("service", service.as_str());
+    }
+}
+
+pub fn record_response_status(status_code: u16) {
+    Span::current().record("http.response.status_code", status_code);
+}
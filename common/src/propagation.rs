@@ -0,0 +1,40 @@
+use std::collections::HashMap;
+
+use aws_sdk_sqs::types::MessageAttributeValue;
+use opentelemetry::propagation::{Extractor, Injector};
+
+/// Carries W3C trace context (`traceparent` + `tracestate`) through SQS message attributes,
+/// mirroring the injector/extractor carrier pattern already used for the outbound HTTP headers,
+/// so transport metadata stops riding along inside the message body.
+pub struct SqsMessageAttributeInjector<'a>(pub &'a mut HashMap<String, MessageAttributeValue>);
+
+impl<'a> Injector for SqsMessageAttributeInjector<'a> {
+    fn set(&mut self, key: &str, value: String) {
+        let attribute = match MessageAttributeValue::builder()
+            .data_type("String")
+            .string_value(value)
+            .build()
+        {
+            Ok(attribute) => attribute,
+            Err(e) => {
+                tracing::warn!("failed to build SQS message attribute {}: {}", key, e);
+                return;
+            }
+        };
+        self.0.insert(key.to_string(), attribute);
+    }
+}
+
+pub struct SqsMessageAttributeExtractor<'a>(pub &'a HashMap<String, MessageAttributeValue>);
+
+impl<'a> Extractor for SqsMessageAttributeExtractor<'a> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0
+            .get(key)
+            .and_then(|attribute| attribute.string_value())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(String::as_str).collect()
+    }
+}
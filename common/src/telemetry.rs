@@ -0,0 +1,103 @@
+use std::env;
+
+use opentelemetry::KeyValue;
+use opentelemetry_datadog::ApiVersion;
+use opentelemetry_sdk::trace::{Config, Sampler, TracerProvider};
+use opentelemetry_sdk::Resource;
+
+/// Builds the process-wide tracer provider, registers it as the global provider, and returns it.
+/// Callers build the `tracing-opentelemetry` tracer from the returned provider and pass the same
+/// provider to `flush_spans`, so both reach the same exporter.
+///
+/// The backend is selected at runtime with `OTEL_EXPORTER` (`datadog` | `otlp`, defaults to
+/// `datadog`). For `otlp`, `OTEL_EXPORTER_OTLP_PROTOCOL` (`grpc` | `http/protobuf`, defaults to
+/// `grpc`) picks the transport and `OTEL_EXPORTER_OTLP_ENDPOINT` gives the collector address.
+pub fn init_tracer_provider() -> TracerProvider {
+    let service_name = env::var("FUNCTION_NAME").expect("FUNCTION_NAME is required");
+    let trace_config = Config::default()
+        .with_sampler(sampler_from_env())
+        .with_resource(Resource::new(vec![KeyValue::new(
+            "service.name",
+            service_name.clone(),
+        )]));
+
+    let provider = match env::var("OTEL_EXPORTER").as_deref() {
+        Ok("otlp") => build_otlp_provider(trace_config),
+        _ => build_datadog_provider(&service_name, trace_config),
+    };
+
+    opentelemetry::global::set_tracer_provider(provider.clone());
+    provider
+}
+
+/// Forces the batch span processor to export whatever it's holding so spans aren't lost when
+/// the Lambda execution environment is frozen between invocations.
+pub fn flush_spans(provider: &TracerProvider) {
+    for result in provider.force_flush() {
+        if let Err(e) = result {
+            tracing::warn!("failed to flush spans: {}", e);
+        }
+    }
+}
+
+/// Builds a sampler from `OTEL_TRACES_SAMPLER` / `OTEL_TRACES_SAMPLER_ARG`, always wrapped in
+/// `ParentBased` so a sampled parent keeps its children sampled regardless of the local decision.
+fn sampler_from_env() -> Sampler {
+    let inner = match env::var("OTEL_TRACES_SAMPLER").as_deref() {
+        Ok("always_on") => Sampler::AlwaysOn,
+        Ok("always_off") => Sampler::AlwaysOff,
+        _ => {
+            let ratio = env::var("OTEL_TRACES_SAMPLER_ARG")
+                .ok()
+                .and_then(|v| v.parse::<f64>().ok())
+                .unwrap_or(1.0);
+            Sampler::TraceIdRatioBased(ratio)
+        }
+    };
+    Sampler::ParentBased(Box::new(inner))
+}
+
+fn build_datadog_provider(service_name: &str, trace_config: Config) -> TracerProvider {
+    let agent_address = env::var("AGENT_ADDRESS").expect("AGENT_ADDRESS is required");
+    let exporter = opentelemetry_datadog::new_pipeline()
+        .with_service_name(service_name)
+        .with_agent_endpoint(format!("http://{}:8126", agent_address))
+        .with_api_version(ApiVersion::Version05)
+        .build_exporter()
+        .unwrap_or_else(|e| panic!("error starting Datadog exporter! {}", e));
+
+    TracerProvider::builder()
+        .with_batch_exporter(exporter, opentelemetry_sdk::runtime::Tokio)
+        .with_config(trace_config)
+        .build()
+}
+
+fn build_otlp_provider(trace_config: Config) -> TracerProvider {
+    use opentelemetry_otlp::WithExportConfig;
+
+    let endpoint = env::var("OTEL_EXPORTER_OTLP_ENDPOINT")
+        .expect("OTEL_EXPORTER_OTLP_ENDPOINT is required");
+
+    let builder = TracerProvider::builder().with_config(trace_config);
+
+    let builder = match env::var("OTEL_EXPORTER_OTLP_PROTOCOL").as_deref() {
+        Ok("http/protobuf") => {
+            let exporter = opentelemetry_otlp::new_exporter()
+                .http()
+                .with_endpoint(endpoint)
+                .build_span_exporter()
+                .unwrap_or_else(|e| panic!("error starting OTLP/http exporter! {}", e));
+            builder.with_batch_exporter(exporter, opentelemetry_sdk::runtime::Tokio)
+        }
+        _ => {
+            let exporter = opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(endpoint)
+                .build_span_exporter()
+                .unwrap_or_else(|e| panic!("error starting OTLP/grpc exporter! {}", e));
+            builder.with_batch_exporter(exporter, opentelemetry_sdk::runtime::Tokio)
+        }
+    };
+
+    builder.build()
+}
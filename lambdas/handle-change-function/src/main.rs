@@ -1,9 +1,12 @@
 use std::{collections::HashMap, env};
 
 use aws_lambda_events::sqs::SqsEvent;
+use aws_sdk_sqs::types::MessageAttributeValue;
+use common::extension;
+use common::propagation::SqsMessageAttributeExtractor;
+use common::telemetry::{flush_spans, init_tracer_provider};
 use lambda_runtime::{LambdaEvent, Runtime};
 use opentelemetry::propagation::TextMapPropagator;
-use opentelemetry_datadog::{new_pipeline, ApiVersion};
 use opentelemetry_sdk::propagation::TraceContextPropagator;
 use serde::Deserialize;
 use tower::{service_fn, BoxError};
@@ -16,55 +19,58 @@ struct MessageBody {
     timestamp: i64,
     description: String,
     id: String,
-    correlation_id: String,
 }
 
 impl std::fmt::Display for MessageBody {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "(Timestamp)={}|(Description)={}|(Id)={}|(CorrelationId)={}",
-            self.timestamp, self.description, self.id, self.correlation_id
+            "(Timestamp)={}|(Description)={}|(Id)={}",
+            self.timestamp, self.description, self.id
         )
     }
 }
 
 #[instrument(name = "Handler")]
 async fn handler(event: LambdaEvent<SqsEvent>) -> Result<(), &'static str> {
-    event.payload.records.into_iter().for_each(|record| {
-        let r: MessageBody = serde_json::from_str(record.body.unwrap().as_ref()).unwrap();
-        let mut fields: HashMap<String, String> = HashMap::new();
-        fields.insert("traceparent".to_string(), r.correlation_id.clone());
+    extension::record_invocation_context(
+        event.context.request_id.clone(),
+        tracing::Span::current().context(),
+    );
 
-        let propagator = TraceContextPropagator::new();
-        let context = propagator.extract(&fields);
-        let span = tracing::Span::current();
-        span.set_parent(context);
-        span.record("otel.kind", "SERVER");
-        tracing::info!("(Body)={}", r.clone());
-        tracing::info_span!("Processing Record");
-    });
-    Ok(())
-}
+    let propagator = TraceContextPropagator::new();
 
-fn init_datadog_pipeline() -> opentelemetry_sdk::trace::Tracer {
-    let agent_address = env::var("AGENT_ADDRESS").expect("AGENT_ADDRESS is required");
-    match new_pipeline()
-        .with_service_name(env::var("FUNCTION_NAME").expect("FUNCTION_NAME is required"))
-        .with_agent_endpoint(format!("http://{}:8126", agent_address))
-        .with_api_version(ApiVersion::Version05)
-        .install_simple()
-    {
-        Ok(a) => a,
-        Err(e) => {
-            panic!("error starting! {}", e);
-        }
+    for record in event.payload.records {
+        let r: MessageBody = serde_json::from_str(record.body.as_deref().unwrap()).unwrap();
+        let message_attributes: HashMap<String, MessageAttributeValue> = record
+            .message_attributes
+            .iter()
+            .filter_map(|(key, attribute)| {
+                let value = attribute.string_value.clone()?;
+                let value = MessageAttributeValue::builder()
+                    .data_type("String")
+                    .string_value(value)
+                    .build()
+                    .ok()?;
+                Some((key.clone(), value))
+            })
+            .collect();
+        let context = propagator.extract(&SqsMessageAttributeExtractor(&message_attributes));
+
+        let span = tracing::info_span!("Processing Record", otel.kind = "CONSUMER");
+        span.set_parent(context);
+        let _enter = span.enter();
+        tracing::info!("(Body)={}", r);
     }
+    Ok(())
 }
 
 #[tokio::main]
 async fn main() -> Result<(), BoxError> {
-    let telemetry_layer = tracing_opentelemetry::layer().with_tracer(init_datadog_pipeline());
+    let provider = init_tracer_provider();
+    extension::spawn(provider.clone());
+    let tracer = provider.tracer(env::var("FUNCTION_NAME").expect("FUNCTION_NAME is required"));
+    let telemetry_layer = tracing_opentelemetry::layer().with_tracer(tracer);
     let fmt_layer = tracing_subscriber::fmt::layer()
         .json()
         .with_target(false)
@@ -76,7 +82,14 @@ async fn main() -> Result<(), BoxError> {
         .with(fmt_layer)
         .with(tracing_subscriber::EnvFilter::from_default_env())
         .init();
-    let runtime = Runtime::new(service_fn(handler));
+    let runtime = Runtime::new(service_fn(move |event| {
+        let provider = provider.clone();
+        async move {
+            let result = handler(event).await;
+            flush_spans(&provider);
+            result
+        }
+    }));
     runtime.run().await?;
     Ok(())
 }
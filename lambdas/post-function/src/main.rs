@@ -2,10 +2,14 @@ use std::{collections::HashMap, env};
 
 use aws_config::BehaviorVersion;
 use aws_sdk_sqs::operation::send_message::SendMessageError;
-use lambda_http::{run, service_fn, Body, Request, Response};
+use aws_sdk_sqs::types::MessageAttributeValue;
+use common::extension;
+use common::middleware::{record_response_status, record_server_span};
+use common::propagation::SqsMessageAttributeInjector;
+use common::telemetry::{flush_spans, init_tracer_provider};
+use lambda_http::{run, service_fn, Body, Request, RequestExt, Response};
 use opentelemetry::global;
 use opentelemetry::propagation::TextMapPropagator;
-use opentelemetry_datadog::{new_pipeline, ApiVersion};
 use opentelemetry_sdk::propagation::TraceContextPropagator;
 use reqwest::header::{HeaderName, HeaderValue};
 use reqwest_middleware::ClientBuilder;
@@ -27,13 +31,15 @@ struct MessageBody {
     timestamp: i64,
     description: String,
     id: String,
-    correlation_id: String,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), lambda_http::Error> {
     global::set_text_map_propagator(opentelemetry_sdk::propagation::TraceContextPropagator::new());
-    let telemetry_layer = tracing_opentelemetry::layer().with_tracer(init_datadog_pipeline());
+    let provider = init_tracer_provider();
+    extension::spawn(provider.clone());
+    let tracer = provider.tracer(env::var("FUNCTION_NAME").expect("FUNCTION_NAME is required"));
+    let telemetry_layer = tracing_opentelemetry::layer().with_tracer(tracer);
     let fmt_layer = tracing_subscriber::fmt::layer()
         .json()
         .with_target(false)
@@ -58,59 +64,56 @@ async fn main() -> Result<(), lambda_http::Error> {
     let shared_service_url = service_url.as_str();
     let shared_queue_url = queue_url.as_str();
 
-    run(service_fn(move |event: Request| async move {
-        handler(
-            shared_client,
-            shared_http_client,
-            shared_service_url,
-            shared_queue_url,
-            event,
-        )
-        .await
+    run(service_fn(move |event: Request| {
+        let provider = provider.clone();
+        async move {
+            let result = handler(
+                shared_client,
+                shared_http_client,
+                shared_service_url,
+                shared_queue_url,
+                event,
+            )
+            .await;
+            flush_spans(&provider);
+            result
+        }
     }))
     .await
 }
 
-fn init_datadog_pipeline() -> opentelemetry_sdk::trace::Tracer {
-    let agent_address = env::var("AGENT_ADDRESS").expect("AGENT_ADDRESS is required");
-    match new_pipeline()
-        .with_service_name(env::var("FUNCTION_NAME").expect("FUNCTION_NAME is required"))
-        .with_agent_endpoint(format!("http://{}:8126", agent_address))
-        .with_api_version(ApiVersion::Version05)
-        .install_simple()
-    {
-        Ok(a) => a,
-        Err(e) => {
-            panic!("error starting! {}", e);
-        }
-    }
-}
-
-#[instrument(name = "Handler")]
+common::instrument_handler! {
 async fn handler(
     client: &aws_sdk_sqs::Client,
     http_client: &reqwest_middleware::ClientWithMiddleware,
     service_url: &str,
     queue_url: &str,
-    _request: Request,
+    request: Request,
 ) -> Result<Response<Body>, lambda_http::Error> {
+    // lambda_http doesn't expose the API Gateway route template on `Request`, so `http.route`
+    // is left unset rather than duplicating the concrete path into it.
+    record_server_span(
+        request.headers(),
+        request.method().as_str(),
+        None,
+        request.uri().path(),
+    );
+    extension::record_invocation_context(
+        request.lambda_context().request_id.clone(),
+        tracing::Span::current().context(),
+    );
+
     let ctx = Span::current().context();
     let propagator = TraceContextPropagator::new();
     let mut fields = HashMap::new();
-
-    let mut trace_parent: Option<String> = None;
-
     propagator.inject_context(&ctx, &mut fields);
     let headers = fields
         .into_iter()
         .map(|(k, v)| {
-            if k == "traceparent" {
-                trace_parent = Some(v.clone());
-            }
-            return (
+            (
                 HeaderName::try_from(k).unwrap(),
                 HeaderValue::try_from(v).unwrap(),
-            );
+            )
         })
         .collect();
 
@@ -132,9 +135,7 @@ async fn handler(
                             timestamp: b.timestamp,
                             description: b.description,
                             id: Uuid::new_v4().to_string(),
-                            correlation_id: "".to_string(),
                         },
-                        trace_parent,
                     )
                     .await;
                 }
@@ -147,33 +148,35 @@ async fn handler(
             tracing::error!("(Error)={}", e);
         }
     }
+    let status_code: u16 = 200;
+    record_response_status(status_code);
     let response = Response::builder()
-        .status(200)
+        .status(status_code)
         .header("Content-Type", "application/json")
         .body("".into())
         .map_err(Box::new)?;
     Ok(response)
 }
+}
 
 #[instrument(name = "Post Message")]
 async fn post_message(
     client: &aws_sdk_sqs::Client,
     queue_url: &str,
-    mut payload: MessageBody,
-    trace_parent: Option<String>,
+    payload: MessageBody,
 ) -> Result<(), aws_sdk_sqs::error::SdkError<SendMessageError>> {
-    match trace_parent {
-        Some(x) => {
-            payload.correlation_id = x;
-        }
-        None => payload.correlation_id = "".to_string(),
-    }
+    let ctx = Span::current().context();
+    let propagator = TraceContextPropagator::new();
+    let mut message_attributes: HashMap<String, MessageAttributeValue> = HashMap::new();
+    propagator.inject_context(&ctx, &mut SqsMessageAttributeInjector(&mut message_attributes));
+
     let span = tracing::info_span!("SQS");
     let message = serde_json::to_string(&payload).unwrap();
     client
         .send_message()
         .queue_url(queue_url)
         .message_body(&message)
+        .set_message_attributes(Some(message_attributes))
         .send()
         .instrument(span)
         .await?;
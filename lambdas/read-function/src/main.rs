@@ -1,14 +1,14 @@
-use std::{collections::HashMap, env};
+use std::env;
 
 use aws_lambda_events::{
     apigw::{ApiGatewayProxyRequest, ApiGatewayProxyResponse},
     http::HeaderMap,
 };
 use chrono::Utc;
+use common::extension;
+use common::middleware::{record_response_status, record_server_span};
+use common::telemetry::{flush_spans, init_tracer_provider};
 use lambda_runtime::{Error, LambdaEvent, Runtime};
-use opentelemetry::propagation::TextMapPropagator;
-use opentelemetry_datadog::{new_pipeline, ApiVersion};
-use opentelemetry_sdk::propagation::TraceContextPropagator;
 use serde::{Deserialize, Serialize};
 use tower::service_fn;
 use tracing::instrument;
@@ -23,7 +23,10 @@ struct AddedContext {
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
-    let telemetry_layer = tracing_opentelemetry::layer().with_tracer(init_datadog_pipeline());
+    let provider = init_tracer_provider();
+    extension::spawn(provider.clone());
+    let tracer = provider.tracer(env::var("FUNCTION_NAME").expect("FUNCTION_NAME is required"));
+    let telemetry_layer = tracing_opentelemetry::layer().with_tracer(tracer);
     let fmt_layer = tracing_subscriber::fmt::layer()
         .json()
         .with_target(false)
@@ -36,26 +39,18 @@ async fn main() -> Result<(), Error> {
         .with(tracing_subscriber::EnvFilter::from_default_env())
         .init();
     // Initialize the Lambda runtime and add OpenTelemetry tracing
-    let runtime = Runtime::new(service_fn(handler));
+    let runtime = Runtime::new(service_fn(move |event| {
+        let provider = provider.clone();
+        async move {
+            let result = handler(event).await;
+            flush_spans(&provider);
+            result
+        }
+    }));
     runtime.run().await?;
     Ok(())
 }
 
-fn init_datadog_pipeline() -> opentelemetry_sdk::trace::Tracer {
-    let agent_address = env::var("AGENT_ADDRESS").expect("AGENT_ADDRESS is required");
-    match new_pipeline()
-        .with_service_name(env::var("FUNCTION_NAME").expect("FUNCTION_NAME is required"))
-        .with_agent_endpoint(format!("http://{}:8126", agent_address))
-        .with_api_version(ApiVersion::Version05)
-        .install_simple()
-    {
-        Ok(a) => a,
-        Err(e) => {
-            panic!("error starting! {}", e);
-        }
-    }
-}
-
 #[instrument(name = "AddContext")]
 fn generate_context() -> AddedContext {
     AddedContext {
@@ -64,32 +59,26 @@ fn generate_context() -> AddedContext {
     }
 }
 
-#[instrument(name = "Handler")]
+common::instrument_handler! {
 async fn handler(
     request: LambdaEvent<ApiGatewayProxyRequest>,
 ) -> Result<ApiGatewayProxyResponse, Error> {
-    let mut fields: HashMap<String, String> = HashMap::new();
-    fields.insert(
-        "traceparent".to_string(),
-        String::from(
-            request
-                .payload
-                .headers
-                .get("traceparent")
-                .unwrap()
-                .to_str()
-                .unwrap(),
-        ),
+    record_server_span(
+        &request.payload.headers,
+        &request.payload.http_method.to_string(),
+        request.payload.resource.as_deref(),
+        request.payload.path.as_deref().unwrap_or_default(),
+    );
+    extension::record_invocation_context(
+        request.context.request_id.clone(),
+        tracing::Span::current().context(),
     );
 
-    let propagator = TraceContextPropagator::new();
-    let context = propagator.extract(&fields);
-    let span = tracing::Span::current();
-    span.set_parent(context);
     let s = generate_context();
     let out = serde_json::to_string(&s).unwrap();
     let mut headers = HeaderMap::new();
     headers.insert("Content-Type", "application/json".parse().unwrap());
+    record_response_status(200);
     Ok(ApiGatewayProxyResponse {
         body: Some(out.into()),
         headers,
@@ -98,3 +87,4 @@ async fn handler(
         multi_value_headers: HeaderMap::new(),
     })
 }
+}